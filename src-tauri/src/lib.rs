@@ -1,42 +1,426 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use rayon::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tauri::{AppHandle, Emitter, Manager};
 
+/// 一个目录（或文件）的大小聚合结果：(表观大小, 实际占用大小, 文件数)
+/// Aggregated size result for a directory (or file): (apparent size, allocated size, file count).
+/// Both numbers are always tracked together so the UI can switch `SizeMode` without rescanning.
+type SizeResult = (u64, u64, u64);
+
+/// 持久化缓存文件的名称，保存在应用数据目录下
+/// Name of the persisted cache file, stored under the app data directory.
+const CACHE_FILE_NAME: &str = "size-cache.json";
+
+/// 目录内某个直接文件的缓存元数据，用于检测该文件的原地修改
+/// Cached metadata for one direct file in a directory, used to detect an in-place edit to it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct CachedFileMeta {
+    name: String,
+    apparent_size: u64,
+    allocated_size: u64,
+    mtime: u64,
+}
+
+/// 单个目录的缓存条目：聚合结果、自身贡献、直接文件元数据与子目录列表
+/// A per-directory cache entry: the recursive aggregate, this directory's own contribution,
+/// each direct file's metadata, and its subdirectory list.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct CacheEntry {
+    apparent_size: u64,
+    allocated_size: u64,
+    file_count: u64,
+    mtime: u64,
+    own_apparent: u64,
+    own_allocated: u64,
+    own_count: u64,
+    own_files: Vec<CachedFileMeta>,
+    subdirs: Vec<String>,
+}
+
+/// 目录本身的修改时间（秒级 Unix 时间戳），只在目录内直接新增/删除条目时变化
+/// A directory's own modification time (Unix seconds), which only changes when an entry is
+/// directly added to or removed from it.
+fn dir_mtime_secs(path: &Path) -> Option<u64> {
+    fs::metadata(path)
+        .ok()?
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs())
+}
+
+/// 确认缓存的每个直接文件的大小和 mtime 都和当前磁盘状态一致
+/// Confirm every cached direct file still matches its current on-disk size and mtime.
+fn own_files_unchanged(dir: &Path, own_files: &[CachedFileMeta]) -> bool {
+    own_files.iter().all(|cached| {
+        let file_path = dir.join(&cached.name);
+        match fs::symlink_metadata(&file_path) {
+            Ok(meta) if !meta.is_dir() => meta.len() == cached.apparent_size && file_mtime_secs(&meta) == Some(cached.mtime),
+            _ => false,
+        }
+    })
+}
+
+/// 从已经取得的（symlink_metadata 得到的）元数据中提取 mtime，避免再次 stat，
+/// 对软链接也使用链接自身的 mtime 而不是其目标的
+/// Extract mtime from metadata already obtained (via `symlink_metadata`), avoiding a second
+/// stat, and — for symlinks — using the link's own mtime rather than its target's.
+fn file_mtime_secs(meta: &fs::Metadata) -> Option<u64> {
+    meta.modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs())
+}
+
+/// 大小计算模式：表观大小（逻辑字节数）还是实际占用的磁盘空间
+/// Size computation mode: the apparent (logical) size, or the actual space allocated on disk.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeMode {
+    Apparent,
+    Allocated,
+}
+
+/// 文件类型分类：目录、常规文件、软链接，或几种特殊文件；仿照 Mercurial 对 `BadType`
+/// 的分类方式，让前端能分别展示而不是把所有非目录条目都当成普通文件
+/// File type classification: directory, regular file, symlink, or one of a few special file
+/// types — modelled on Mercurial's `BadType` classification, so the frontend can display them
+/// distinctly instead of treating every non-directory entry as a plain file.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    Regular,
+    Symlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+    Directory,
+}
+
+/// 根据元数据判断文件类型；必须传入 `symlink_metadata` 的结果，否则软链接会被
+/// 误判为其指向目标的类型
+/// Classify a metadata's file type. Must be called with the result of `symlink_metadata`,
+/// otherwise a symlink would be misclassified as whatever it points to.
+#[cfg(unix)]
+fn classify_file_kind(meta: &fs::Metadata) -> FileKind {
+    use std::os::unix::fs::FileTypeExt;
+    let file_type = meta.file_type();
+    if file_type.is_dir() {
+        FileKind::Directory
+    } else if file_type.is_symlink() {
+        FileKind::Symlink
+    } else if file_type.is_fifo() {
+        FileKind::Fifo
+    } else if file_type.is_socket() {
+        FileKind::Socket
+    } else if file_type.is_block_device() {
+        FileKind::BlockDevice
+    } else if file_type.is_char_device() {
+        FileKind::CharDevice
+    } else {
+        FileKind::Regular
+    }
+}
+
+#[cfg(not(unix))]
+fn classify_file_kind(meta: &fs::Metadata) -> FileKind {
+    let file_type = meta.file_type();
+    if file_type.is_dir() {
+        FileKind::Directory
+    } else if file_type.is_symlink() {
+        FileKind::Symlink
+    } else {
+        FileKind::Regular
+    }
+}
+
+/// 提取 (设备号, inode)，用于 hard-link 去重：两个路径若设备号和 inode 都相同，
+/// 就指向同一份磁盘数据。非 Unix 平台没有对应概念，始终返回 None（此时去重直接关闭）
+/// Extract (device, inode) for hard-link dedup: two paths sharing both a device and an inode
+/// point at the same underlying data. Not available on non-Unix platforms, where this always
+/// returns `None` (hard-link dedup is simply inert there).
+#[cfg(unix)]
+fn dev_ino(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn dev_ino(_meta: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// 导出格式：完整的嵌套 JSON，或扁平化的 CSV
+/// Export format: the full nested JSON tree, or a flattened CSV.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+/// 某个路径实际占用的磁盘空间；正确处理稀疏文件、文件系统块对齐以及透明压缩
+/// (如 zstd 压缩的文件系统）导致的存储字节数与逻辑字节数不一致的情况。
+/// The actual on-disk space a path occupies; correctly accounts for sparse files, filesystem
+/// block rounding, and transparently-compressed files where stored bytes differ from logical
+/// bytes (e.g. a zstd-compressed filesystem).
+#[cfg(unix)]
+fn compute_allocated_size(_path: &Path, meta: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.blocks() * 512
+}
+
+#[cfg(windows)]
+fn compute_allocated_size(path: &Path, meta: &fs::Metadata) -> u64 {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Foundation::{GetLastError, NO_ERROR};
+    use windows_sys::Win32::Storage::FileSystem::GetCompressedFileSizeW;
+
+    let wide: Vec<u16> = path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut high: u32 = 0;
+    let low = unsafe { GetCompressedFileSizeW(wide.as_ptr(), &mut high) };
+    // INVALID_FILE_SIZE (u32::MAX) is only an error when GetLastError() is set; a file whose
+    // low DWORD is legitimately 0xFFFFFFFF reports that value with NO_ERROR.
+    if low == u32::MAX && unsafe { GetLastError() } != NO_ERROR {
+        meta.len()
+    } else {
+        (u64::from(high) << 32) | u64::from(low)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn compute_allocated_size(_path: &Path, meta: &fs::Metadata) -> u64 {
+    meta.len()
+}
+
+/// 部分哈希读取的字节数（16 KiB），足以在大多数情况下区分不同内容
+/// Number of bytes read for the cheap "partial hash" pass (16 KiB) — enough to split most groups.
+const PARTIAL_HASH_BYTES: usize = 16 * 1024;
+
+/// 根据请求的线程数构建专用 rayon 线程池，0 表示“自动”（使用可用的并行度）。
+/// 旋转磁盘上过度订阅 I/O 线程反而会拖慢目录遍历，所以请求值永远不会超过可用并行度。
+/// Build a dedicated rayon thread pool for the requested thread count. 0 means "auto" (use
+/// available parallelism). The requested value is clamped to the available parallelism and
+/// never goes below 1, since over-subscribing I/O threads on spinning disks actually slows
+/// directory walking down.
+fn build_thread_pool(requested: usize) -> rayon::ThreadPool {
+    let available = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    let threads = if requested == 0 {
+        available
+    } else {
+        requested.min(available).max(1)
+    };
+
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("failed to build rayon thread pool")
+}
+
 /// 应用程序状态（全局共享）
 /// App state (shared globally)
 struct AppState {
-    /// 简单结果缓存：只存储最终计算结果
-    /// Simple result cache: stores final results only
-    /// Key: path, Value: (size, file_count)
-    size_cache: Arc<Mutex<HashMap<String, (u64, u64)>>>,
+    /// 结果缓存：既用于加速，也持久化到磁盘（见 load_persisted_cache / persist_cache）
+    /// Key: 规范化路径, Value: 缓存条目（含 mtime，用于判断是否需要重新扫描）
+    /// Result cache: speeds up rescans and is persisted to disk (see `load_persisted_cache` /
+    /// `persist_cache`). Key: normalized path, Value: a cache entry carrying the directory's
+    /// mtime so we know whether it needs rescanning.
+    size_cache: Arc<Mutex<HashMap<String, CacheEntry>>>,
     /// 进行中的计算集合，用于避免重复启动后台计算
     /// In-progress set to prevent duplicated background computations
     in_progress: Arc<Mutex<HashSet<String>>>,
+    /// 按规范化根路径分别保存的协作式取消标志，让针对一个路径的取消不会影响
+    /// 其他正在并发扫描的路径
+    /// Cooperative cancellation flags keyed by normalized root path, so cancelling one path
+    /// doesn't affect another path being scanned concurrently.
+    cancel_flags: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+    /// 等待被 ticker 线程批量推送给前端的目录大小更新
+    /// Pending per-directory size updates waiting to be flushed by the ticker thread.
+    pending_updates: Arc<Mutex<HashMap<String, SizeResult>>>,
+    /// ticker 线程已经推送的批次数，主要用于诊断/测试
+    /// Number of batches the ticker thread has flushed so far; mostly useful for diagnostics.
+    tick_counter: Arc<AtomicUsize>,
+    /// 用于目录遍历的专用 rayon 线程池；通过 set_thread_count 重建并持久化这一选择，
+    /// 避免每次扫描都重新创建线程池
+    /// Dedicated rayon thread pool used for directory traversal. Rebuilt by `set_thread_count`
+    /// and persisted here so repeated scans don't rebuild the pool each time.
+    thread_pool: Arc<Mutex<Arc<rayon::ThreadPool>>>,
+    /// 持久化缓存文件的完整路径（应用数据目录 + CACHE_FILE_NAME）
+    /// Full path to the persisted cache file (app data dir + CACHE_FILE_NAME).
+    cache_file: Option<PathBuf>,
+    /// 自上次落盘以来缓存是否被修改过；用于防抖刷新线程跳过空闲时的写入
+    /// Whether the cache has changed since it was last flushed; lets the debounced flush
+    /// thread skip writes while idle.
+    cache_dirty: Arc<AtomicBool>,
+}
+
+/// 从应用数据目录加载持久化的缓存；文件不存在或内容损坏时返回空缓存
+/// Load the persisted cache from the app data directory; returns an empty cache if the file
+/// doesn't exist or its contents can't be parsed.
+fn load_persisted_cache(cache_file: &Path) -> HashMap<String, CacheEntry> {
+    fs::read_to_string(cache_file)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// 把缓存写回磁盘；目录不存在时先创建
+/// Flush the cache to disk, creating the parent directory first if needed.
+fn persist_cache(cache_file: &Path, cache: &HashMap<String, CacheEntry>) {
+    if let Some(parent) = cache_file.parent() {
+        if fs::create_dir_all(parent).is_err() {
+            return;
+        }
+    }
+    if let Ok(json) = serde_json::to_string(cache) {
+        let _ = fs::write(cache_file, json);
+    }
 }
 
 #[derive(Serialize, Clone, Debug)]
 pub struct FileNode {
     name: String,
     path: String,
-    size: Option<u64>, // None 表示“计算中” / None means "calculating"
-    base_size: u64,    // 当前目录下直接文件大小总和 / Direct files total size
+    size: Option<u64>, // 按所选 SizeMode 得出的总大小；None 表示“计算中” / Total size under the selected SizeMode; None means "calculating"
+    base_size: u64,    // 当前目录下直接文件大小总和（同样按所选 SizeMode） / Direct files total size (also under the selected SizeMode)
+    apparent_size: Option<u64>, // 表观（逻辑）大小，始终与 allocated_size 一起维护 / Apparent (logical) size, always tracked alongside allocated_size
+    allocated_size: Option<u64>, // 实际占用的磁盘空间 / Actual space allocated on disk
     is_dir: bool,
     file_count: u64,
     children: Option<Vec<FileNode>>,
+    excluded: bool, // 被排除规则命中，前端据此灰显 / Matched an exclude rule; frontend greys it out
+    kind: FileKind, // 文件类型分类，见 classify_file_kind / File type classification, see classify_file_kind
+}
+
+/// 用户 glob 规则 + 可选 .gitignore 的排除规则集合
+/// Combined exclusion rules: user-supplied globs plus an optional .gitignore pass.
+struct ExcludeRules {
+    globs: GlobSet,
+    respect_gitignore: bool,
+    root: PathBuf,
+}
+
+impl ExcludeRules {
+    fn new(exclude_globs: &[String], respect_gitignore: bool, root: &Path) -> Self {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in exclude_globs {
+            if let Ok(glob) = Glob::new(pattern) {
+                builder.add(glob);
+            }
+        }
+        let globs = builder
+            .build()
+            .unwrap_or_else(|_| GlobSetBuilder::new().build().unwrap());
+        Self {
+            globs,
+            respect_gitignore,
+            root: root.to_path_buf(),
+        }
+    }
+
+    /// 是否启用了任何排除规则；用于决定是否可以信任缓存
+    /// Whether any rule is active at all — used to decide whether the cache can be trusted.
+    fn is_active(&self) -> bool {
+        !self.globs.is_empty() || self.respect_gitignore
+    }
+
+    /// 对相对于扫描根目录的路径和文件名分别做匹配，这样像 `node_modules` 这样不含路径
+    /// 分隔符的模式才能在任意深度命中（glob 必须匹配被检查路径的整体，所以只拿绝对路径
+    /// 匹配时，这类纯名称模式永远不会命中）。
+    /// Match both the path relative to the scan root and the bare file name, so patterns
+    /// without a path separator — like `node_modules` — hit at any depth (a glob has to match
+    /// the whole path it is tested against, so a bare name never matches an absolute path).
+    fn matches_glob(&self, path: &Path) -> bool {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        if self.globs.is_match(relative) {
+            return true;
+        }
+        path.file_name()
+            .map(|name| self.globs.is_match(name))
+            .unwrap_or(false)
+    }
+}
+
+/// 在给定目录下加载 .gitignore（如果存在），并把它压入继承自父目录的匹配栈
+/// Load `dir/.gitignore` if present and push it onto the matcher stack inherited from the
+/// parent directory, so deeper .gitignore files can override shallower ones.
+fn push_gitignore(dir: &Path, parent_stack: &[Arc<Gitignore>]) -> Vec<Arc<Gitignore>> {
+    let mut stack = parent_stack.to_vec();
+    let gitignore_path = dir.join(".gitignore");
+    if gitignore_path.is_file() {
+        let mut builder = GitignoreBuilder::new(dir);
+        if builder.add(&gitignore_path).is_none() {
+            if let Ok(gi) = builder.build() {
+                stack.push(Arc::new(gi));
+            }
+        }
+    }
+    stack
+}
+
+/// 依次检查匹配栈（从最深的目录开始），让更深层的 .gitignore 规则覆盖较浅层的规则
+/// Check the matcher stack from deepest to shallowest so a nested .gitignore can override an
+/// ignore rule set by one of its ancestors.
+fn is_gitignored(path: &Path, is_dir: bool, stack: &[Arc<Gitignore>]) -> bool {
+    for gi in stack.iter().rev() {
+        let matched = gi.matched(path, is_dir);
+        if matched.is_ignore() {
+            return true;
+        }
+        if matched.is_whitelist() {
+            return false;
+        }
+    }
+    false
+}
+
+fn is_excluded(path: &Path, is_dir: bool, rules: &ExcludeRules, gitignore_stack: &[Arc<Gitignore>]) -> bool {
+    rules.matches_glob(path) || (rules.respect_gitignore && is_gitignored(path, is_dir, gitignore_stack))
 }
 
 #[derive(Serialize, Clone, Debug)]
 struct SizeUpdate {
     path: String,
-    size: u64,
+    apparent_size: u64,
+    allocated_size: u64,
     file_count: u64,
 }
 
+/// 一组内容完全相同的文件
+/// A group of byte-identical files.
+#[derive(Serialize, Clone, Debug)]
+pub struct DuplicateGroup {
+    hash: String,
+    size: u64,
+    paths: Vec<String>,
+    wasted_bytes: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct DuplicateScanProgress {
+    files_processed: u64,
+    bytes_hashed: u64,
+}
+
 /// 规范化路径字符串，避免缓存 key 因路径写法不同而不一致
 /// Normalize a path string to keep cache keys consistent across different representations.
 fn normalize_path_string(path: &str) -> String {
@@ -77,23 +461,212 @@ async fn open_in_explorer(path: String) -> Result<(), String> {
     Ok(())
 }
 
-/// 递归计算目录大小（并行版），并通过事件实时回传结果
-/// Recursively compute directory size in parallel and emit realtime updates via events.
+/// 递归计算目录大小（并行版），把结果写入待发送队列由 ticker 线程批量推送；
+/// 被排除的子树不计入结果也不写入缓存。mtime 与 own_files 均未变时跳过
+/// `read_dir`，复用缓存的“自身贡献”，仍递归子目录；同秒内的 mtime 视为可疑。
+/// `seen_inodes` 用于整次扫描内的硬链接去重，启用时绕开持久化缓存。
+/// Recursively compute directory size in parallel, writing results into a pending-update
+/// queue that a background ticker thread drains and emits in batches (see `run`). Excluded
+/// subtrees don't contribute to the result or get cached. Skips `read_dir` (reusing the
+/// cached "own contribution") when both the directory's mtime and its direct files are
+/// unchanged, still recursing into subdirectories; a same-second mtime is treated as dirty
+/// (dirstate-v2 style). `seen_inodes`, when set, dedups hard links across the whole scan and
+/// bypasses the persisted cache.
 fn compute_dir_size_recursive(
     path_str: String,
-    cache: Arc<Mutex<HashMap<String, (u64, u64)>>>,
+    cache: Arc<Mutex<HashMap<String, CacheEntry>>>,
     app_handle: AppHandle,
-) -> (u64, u64) {
-    {
-        let cache_lock = cache.lock().unwrap();
-        if let Some(res) = cache_lock.get(&path_str) {
-            return *res;
-        }
+    rules: Arc<ExcludeRules>,
+    gitignore_stack: Vec<Arc<Gitignore>>,
+    cancel_flag: Arc<AtomicBool>,
+    pending_updates: Arc<Mutex<HashMap<String, SizeResult>>>,
+    seen_inodes: Option<Arc<Mutex<HashSet<(u64, u64)>>>>,
+    cache_dirty: Arc<AtomicBool>,
+) -> SizeResult {
+    if cancel_flag.load(Ordering::Relaxed) {
+        return (0, 0, 0);
     }
 
     let path_obj = Path::new(&path_str);
-    let mut total_size = 0;
-    let mut total_count = 0;
+    let current_mtime = dir_mtime_secs(path_obj);
+    let skip_cache = rules.is_active() || seen_inodes.is_some();
+
+    let cached_entry = if !skip_cache {
+        cache.lock().unwrap().get(&path_str).cloned()
+    } else {
+        None
+    };
+
+    let now_secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs());
+
+    let own_unchanged = matches!(
+        (&cached_entry, current_mtime, now_secs),
+        (Some(entry), Some(mtime), Some(now)) if entry.mtime == mtime && mtime < now
+    ) && cached_entry
+        .as_ref()
+        .map(|entry| own_files_unchanged(path_obj, &entry.own_files))
+        .unwrap_or(false);
+
+    let gitignore_stack = if rules.respect_gitignore {
+        push_gitignore(path_obj, &gitignore_stack)
+    } else {
+        gitignore_stack
+    };
+
+    let (
+        mut total_apparent,
+        mut total_allocated,
+        mut total_count,
+        own_apparent,
+        own_allocated,
+        own_count,
+        own_files,
+        subdirs,
+    ) = if own_unchanged {
+        let entry = cached_entry.as_ref().unwrap();
+        (
+            entry.own_apparent,
+            entry.own_allocated,
+            entry.own_count,
+            entry.own_apparent,
+            entry.own_allocated,
+            entry.own_count,
+            entry.own_files.clone(),
+            entry.subdirs.clone(),
+        )
+    } else {
+        let mut apparent = 0;
+        let mut allocated = 0;
+        let mut count = 0;
+        let mut own_files = Vec::new();
+        let mut subdirs = Vec::new();
+
+        if let Ok(entries) = fs::read_dir(path_obj) {
+            for entry in entries.flatten() {
+                let entry_path = entry.path();
+                let meta = match fs::symlink_metadata(&entry_path) {
+                    Ok(m) => m,
+                    Err(_) => continue,
+                };
+
+                if is_excluded(&entry_path, meta.is_dir(), &rules, &gitignore_stack) {
+                    continue;
+                }
+
+                if meta.is_dir() {
+                    subdirs.push(entry_path.to_string_lossy().to_string());
+                } else {
+                    let first_link = match (&seen_inodes, dev_ino(&meta)) {
+                        (Some(seen), Some(key)) => seen.lock().unwrap().insert(key),
+                        _ => true,
+                    };
+                    if first_link {
+                        let entry_apparent = meta.len();
+                        let entry_allocated = compute_allocated_size(&entry_path, &meta);
+                        apparent += entry_apparent;
+                        allocated += entry_allocated;
+                        count += 1;
+                        own_files.push(CachedFileMeta {
+                            name: entry.file_name().to_string_lossy().to_string(),
+                            apparent_size: entry_apparent,
+                            allocated_size: entry_allocated,
+                            mtime: file_mtime_secs(&meta).unwrap_or(0),
+                        });
+                    }
+                }
+            }
+        }
+
+        (apparent, allocated, count, apparent, allocated, count, own_files, subdirs)
+    };
+
+    if cancel_flag.load(Ordering::Relaxed) {
+        let result = (total_apparent, total_allocated, total_count);
+        pending_updates.lock().unwrap().insert(path_str, result);
+        return result;
+    }
+
+    let results: Vec<SizeResult> = subdirs
+        .par_iter()
+        .map(|subdir| {
+            if cancel_flag.load(Ordering::Relaxed) {
+                return (0, 0, 0);
+            }
+
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                compute_dir_size_recursive(
+                    subdir.clone(),
+                    cache.clone(),
+                    app_handle.clone(),
+                    rules.clone(),
+                    gitignore_stack.clone(),
+                    cancel_flag.clone(),
+                    pending_updates.clone(),
+                    seen_inodes.clone(),
+                    cache_dirty.clone(),
+                )
+            }));
+
+            match result {
+                Ok(res) => res,
+                Err(_) => {
+                    eprintln!("Panic processing subdir: {}", subdir);
+                    pending_updates
+                        .lock()
+                        .unwrap()
+                        .insert(subdir.clone(), (0, 0, 0));
+                    (0, 0, 0)
+                }
+            }
+        })
+        .collect();
+
+    for (apparent, allocated, count) in results {
+        total_apparent += apparent;
+        total_allocated += allocated;
+        total_count += count;
+    }
+
+    let result = (total_apparent, total_allocated, total_count);
+
+    if !skip_cache {
+        if let Some(mtime) = current_mtime {
+            let mut cache_lock = cache.lock().unwrap();
+            cache_lock.insert(
+                path_str.clone(),
+                CacheEntry {
+                    apparent_size: total_apparent,
+                    allocated_size: total_allocated,
+                    file_count: total_count,
+                    mtime,
+                    own_apparent,
+                    own_allocated,
+                    own_count,
+                    own_files,
+                    subdirs,
+                },
+            );
+            cache_dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pending_updates.lock().unwrap().insert(path_str, result);
+
+    result
+}
+
+/// 并行收集目录下所有常规文件及其大小和 (设备号, inode)（复用与 compute_dir_size_recursive
+/// 相同的遍历方式）；(设备号, inode) 用于在 find_duplicates 中剔除互为硬链接的文件
+/// Recursively collect all regular files under a directory in parallel, the same traversal
+/// shape as `compute_dir_size_recursive`, but returning the flat file list (with each file's
+/// size and (device, inode)) instead of totals. The (device, inode) lets `find_duplicates`
+/// dedup hard links to the same underlying data.
+fn collect_files_recursive(path_str: &str) -> Vec<(String, u64, Option<(u64, u64)>)> {
+    let path_obj = Path::new(path_str);
+    let mut files = Vec::new();
     let mut subdirs = Vec::new();
 
     if let Ok(entries) = fs::read_dir(path_obj) {
@@ -106,75 +679,183 @@ fn compute_dir_size_recursive(
 
             if meta.is_dir() {
                 subdirs.push(entry_path.to_string_lossy().to_string());
-            } else {
-                total_size += meta.len();
-                total_count += 1;
+            } else if meta.is_file() {
+                files.push((
+                    entry_path.to_string_lossy().to_string(),
+                    meta.len(),
+                    dev_ino(&meta),
+                ));
             }
         }
     }
 
-    let results: Vec<(u64, u64)> = subdirs
+    let nested: Vec<Vec<(String, u64, Option<(u64, u64)>)>> = subdirs
         .par_iter()
-        .map(|subdir| {
-            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                compute_dir_size_recursive(subdir.clone(), cache.clone(), app_handle.clone())
-            }));
+        .map(|subdir| collect_files_recursive(subdir))
+        .collect();
 
-            match result {
-                Ok(res) => res,
-                Err(_) => {
-                    eprintln!("Panic processing subdir: {}", subdir);
-                    let _ = app_handle.emit(
-                        "folder-size-updated",
-                        SizeUpdate {
-                            path: subdir.clone(),
-                            size: 0,
-                            file_count: 0,
+    for mut group in nested {
+        files.append(&mut group);
+    }
+
+    files
+}
+
+/// 对文件的前 PARTIAL_HASH_BYTES 字节做哈希，用于在按大小分桶之后做廉价的二次分组
+/// Hash only the first `PARTIAL_HASH_BYTES` of a file — a cheap second-pass grouping key
+/// applied after bucketing by size, before paying for a full hash.
+fn partial_hash(path: &str) -> std::io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut buf = vec![0u8; PARTIAL_HASH_BYTES];
+    let mut total_read = 0;
+    loop {
+        let n = file.read(&mut buf[total_read..])?;
+        if n == 0 {
+            break;
+        }
+        total_read += n;
+        if total_read == buf.len() {
+            break;
+        }
+    }
+    Ok(blake3::hash(&buf[..total_read]).to_hex().to_string())
+}
+
+/// 对整个文件内容做哈希，只在分区仍然存在哈希碰撞时才调用，避免无谓的全量 I/O
+/// Hash a whole file's contents — only called once partial hashes still collide, to avoid
+/// paying full-file I/O for files that are cheaply distinguishable.
+fn full_hash(path: &str, bytes_hashed: &AtomicU64) -> std::io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        bytes_hashed.fetch_add(n as u64, Ordering::Relaxed);
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// 按内容查找重复文件：先按大小分桶，再做部分哈希分组，最后只对仍然碰撞的文件做全量哈希
+/// czkawka 风格的三段式流水线，避免对整棵树做全量哈希。
+/// Find byte-identical files via a czkawka-style three-stage pipeline (size bucket -> partial
+/// hash -> full hash) so we only pay for a full hash on files that still collide after the
+/// cheap passes. Emits `duplicate-scan-progress` events as it goes.
+#[tauri::command]
+async fn find_duplicates(path: String, app: AppHandle) -> Result<Vec<DuplicateGroup>, String> {
+    let root_path = normalize_path_string(&path);
+    let files = collect_files_recursive(&root_path);
+
+    let files_processed = Arc::new(AtomicU64::new(0));
+    let bytes_hashed = Arc::new(AtomicU64::new(0));
+
+    // Hard links sharing the same (device, inode) occupy a single copy of data on disk, so
+    // only the first path seen for a given inode is kept — otherwise they'd be reported as
+    // "duplicates" whose wasted_bytes inflates how much space could actually be reclaimed.
+    let mut seen_inodes: HashSet<(u64, u64)> = HashSet::new();
+    let files: Vec<(String, u64)> = files
+        .into_iter()
+        .filter(|(_, _, inode)| match inode {
+            Some(key) => seen_inodes.insert(*key),
+            None => true,
+        })
+        .map(|(file_path, size, _)| (file_path, size))
+        .collect();
+
+    // Stage 1: bucket by size, dropping any size with a single file.
+    let mut by_size: HashMap<u64, Vec<String>> = HashMap::new();
+    for (file_path, size) in files {
+        by_size.entry(size).or_default().push(file_path);
+    }
+    by_size.retain(|_, paths| paths.len() > 1);
+
+    // Stage 2: within each size bucket, split further by a partial hash of the first bytes.
+    let partial_groups: Vec<(u64, Vec<String>)> = by_size
+        .into_par_iter()
+        .flat_map(|(size, paths)| {
+            let mut by_partial: HashMap<String, Vec<String>> = HashMap::new();
+            for path in paths {
+                if let Ok(hash) = partial_hash(&path) {
+                    by_partial.entry(hash).or_default().push(path);
+                }
+                let processed = files_processed.fetch_add(1, Ordering::Relaxed) + 1;
+                if processed % 200 == 0 {
+                    let _ = app.emit(
+                        "duplicate-scan-progress",
+                        DuplicateScanProgress {
+                            files_processed: processed,
+                            bytes_hashed: bytes_hashed.load(Ordering::Relaxed),
                         },
                     );
-                    (0, 0)
                 }
             }
+            by_partial
+                .into_values()
+                .filter(|paths| paths.len() > 1)
+                .map(|paths| (size, paths))
+                .collect::<Vec<_>>()
         })
         .collect();
 
-    for (s, c) in results {
-        total_size += s;
-        total_count += c;
-    }
+    // Stage 3: only files still colliding after the partial hash get a full hash.
+    let mut groups: Vec<DuplicateGroup> = partial_groups
+        .into_par_iter()
+        .flat_map(|(size, paths)| {
+            let mut by_full: HashMap<String, Vec<String>> = HashMap::new();
+            for path in paths {
+                if let Ok(hash) = full_hash(&path, &bytes_hashed) {
+                    by_full.entry(hash).or_default().push(path);
+                }
+            }
+            by_full
+                .into_iter()
+                .filter(|(_, paths)| paths.len() > 1)
+                .map(|(hash, paths)| {
+                    let wasted_bytes = size * (paths.len() as u64 - 1);
+                    DuplicateGroup {
+                        hash,
+                        size,
+                        paths,
+                        wasted_bytes,
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
 
-    {
-        let mut cache_lock = cache.lock().unwrap();
-        cache_lock.insert(path_str.clone(), (total_size, total_count));
-    }
+    groups.sort_by(|a, b| b.wasted_bytes.cmp(&a.wasted_bytes));
 
-    let _ = app_handle.emit(
-        "folder-size-updated",
-        SizeUpdate {
-            path: path_str,
-            size: total_size,
-            file_count: total_count,
+    let _ = app.emit(
+        "duplicate-scan-progress",
+        DuplicateScanProgress {
+            files_processed: files_processed.load(Ordering::Relaxed),
+            bytes_hashed: bytes_hashed.load(Ordering::Relaxed),
         },
     );
 
-    (total_size, total_count)
+    Ok(groups)
 }
 
 /// 判断是否需要启动后台计算，并在需要时标记为 in-progress
+///
+/// 即使根目录已有缓存，也总是启动后台计算：如果缓存仍然有效，
+/// `compute_dir_size_recursive` 只需沿着已缓存的子目录列表重新 stat 每个目录及其直接文件
+/// （不调用 read_dir），代价很低；这样才能在“表面未变但更深层已变化”时也正确刷新结果。
 /// Decide whether to start a background computation and mark it as in-progress when needed.
+///
+/// A background computation is always started even if the root already has a cache entry:
+/// when the cache is still valid, `compute_dir_size_recursive` only re-stats each directory
+/// along the cached subdirectory list and its direct files (no `read_dir`), so the cost is
+/// low — and this is the only
+/// way to correctly pick up changes that happened deeper in the tree without touching the
+/// root's own mtime.
 fn try_mark_in_progress(
     normalized_path: &str,
-    cache: &Arc<Mutex<HashMap<String, (u64, u64)>>>,
     in_progress: &Arc<Mutex<HashSet<String>>>,
 ) -> bool {
-    let cache_hit = {
-        let cache = cache.lock().unwrap();
-        cache.get(normalized_path).is_some()
-    };
-    if cache_hit {
-        return false;
-    }
-
     let mut in_progress = in_progress.lock().unwrap();
     if in_progress.contains(normalized_path) {
         return false;
@@ -184,16 +865,55 @@ fn try_mark_in_progress(
     true
 }
 
+/// 取消指定路径正在进行的扫描；其他路径的并发扫描不受影响
+/// Cancel the scan in flight for the given path, without affecting other paths being scanned
+/// concurrently.
+#[tauri::command]
+async fn cancel_scan(path: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let normalized = normalize_path_string(&path);
+    if let Some(flag) = state.cancel_flags.lock().unwrap().get(&normalized) {
+        flag.store(true, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+/// 重建用于目录遍历的专用线程池；`n` 为 0 表示“自动”（使用可用并行度）
+/// Rebuild the dedicated thread pool used for directory traversal; `n` of 0 means "auto"
+/// (use available parallelism).
+#[tauri::command]
+async fn set_thread_count(n: usize, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let pool = build_thread_pool(n);
+    *state.thread_pool.lock().unwrap() = Arc::new(pool);
+    Ok(())
+}
+
 /// 快速扫描目录结构，并启动后台任务计算目录大小
+///
+/// `count_hardlinks_once` 为 true 时，共享同一份数据的多个硬链接在后台递归计算中只会
+/// 被计入一次（见 compute_dir_size_recursive 上的说明）；这次扫描会绕开持久化缓存。
 /// Quickly scan the directory structure and start background size computations.
+///
+/// When `count_hardlinks_once` is true, multiple hard links sharing the same underlying data
+/// are only counted once by the background recursive computation (see the note on
+/// `compute_dir_size_recursive`); this scan bypasses the persisted cache.
 #[tauri::command]
 async fn analyze_directory(
     path: String,
+    exclude_globs: Vec<String>,
+    respect_gitignore: bool,
+    size_mode: SizeMode,
+    count_hardlinks_once: bool,
     state: tauri::State<'_, AppState>,
     app: AppHandle,
 ) -> Result<FileNode, String> {
     let root_path = normalize_path_string(&path);
     let path_obj = Path::new(&root_path);
+    let rules = Arc::new(ExcludeRules::new(&exclude_globs, respect_gitignore, path_obj));
+    let gitignore_stack = if respect_gitignore {
+        push_gitignore(path_obj, &[])
+    } else {
+        Vec::new()
+    };
     let mut children = Vec::new();
     let mut current_dir_base_size: u64 = 0;
 
@@ -205,39 +925,63 @@ async fn analyze_directory(
                 Err(_) => continue,
             };
             let is_dir = meta.is_dir();
+            let kind = classify_file_kind(&meta);
+            let excluded = is_excluded(&entry_path, is_dir, &rules, &gitignore_stack);
             let path_str = entry_path.to_string_lossy().to_string();
-            let file_size = if is_dir { 0 } else { meta.len() };
 
-            let mut size = if is_dir { None } else { Some(file_size) };
+            let mut apparent_size = if is_dir { None } else { Some(meta.len()) };
+            let mut allocated_size = if is_dir {
+                None
+            } else {
+                Some(compute_allocated_size(&entry_path, &meta))
+            };
             let mut file_count = if is_dir { 0 } else { 1 };
 
-            let node_base_size = if is_dir {
+            let node_base_size = if excluded || is_dir {
                 0
             } else {
+                let file_size = match size_mode {
+                    SizeMode::Apparent => apparent_size.unwrap_or(0),
+                    SizeMode::Allocated => allocated_size.unwrap_or(0),
+                };
                 current_dir_base_size += file_size;
                 file_size
             };
 
-            if is_dir {
+            if excluded {
+                apparent_size = Some(0);
+                allocated_size = Some(0);
+                file_count = 0;
+            } else if is_dir && !rules.is_active() && !count_hardlinks_once {
                 let cache_hit = {
                     let cache = state.size_cache.lock().unwrap();
                     cache.get(&path_str).cloned()
                 };
 
-                if let Some((cached_size, cached_count)) = cache_hit {
-                    size = Some(cached_size);
-                    file_count = cached_count;
+                if let Some(entry) = cache_hit {
+                    apparent_size = Some(entry.apparent_size);
+                    allocated_size = Some(entry.allocated_size);
+                    file_count = entry.file_count;
                 }
             }
 
+            let size = match size_mode {
+                SizeMode::Apparent => apparent_size,
+                SizeMode::Allocated => allocated_size,
+            };
+
             children.push(FileNode {
                 name: entry.file_name().to_string_lossy().to_string(),
                 path: path_str,
                 size,
                 base_size: node_base_size,
+                apparent_size,
+                allocated_size,
                 is_dir,
                 file_count,
                 children: None,
+                excluded,
+                kind,
             });
         }
     }
@@ -263,22 +1007,51 @@ async fn analyze_directory(
         }
     });
 
-    let should_compute_root =
-        try_mark_in_progress(&root_path, &state.size_cache, &state.in_progress);
+    let should_compute_root = try_mark_in_progress(&root_path, &state.in_progress);
 
     if should_compute_root {
+        let cancel_flag = Arc::new(AtomicBool::new(false));
+        state
+            .cancel_flags
+            .lock()
+            .unwrap()
+            .insert(root_path.clone(), cancel_flag.clone());
+
         let cache = state.size_cache.clone();
         let in_progress = state.in_progress.clone();
+        let cancel_flags = state.cancel_flags.clone();
         let app_handle = app.clone();
         let root_to_compute = root_path.clone();
+        let rules = rules.clone();
+        let pending_updates = state.pending_updates.clone();
+        let pool = state.thread_pool.lock().unwrap().clone();
+        let cache_dirty = state.cache_dirty.clone();
+        let seen_inodes = if count_hardlinks_once {
+            Some(Arc::new(Mutex::new(HashSet::new())))
+        } else {
+            None
+        };
 
         thread::spawn(move || {
             let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                compute_dir_size_recursive(root_to_compute.clone(), cache, app_handle);
+                pool.install(|| {
+                    compute_dir_size_recursive(
+                        root_to_compute.clone(),
+                        cache,
+                        app_handle,
+                        rules,
+                        Vec::new(),
+                        cancel_flag,
+                        pending_updates,
+                        seen_inodes,
+                        cache_dirty,
+                    )
+                });
             }));
 
             let mut in_progress = in_progress.lock().unwrap();
             in_progress.remove(&root_to_compute);
+            cancel_flags.lock().unwrap().remove(&root_to_compute);
         });
     }
 
@@ -287,37 +1060,314 @@ async fn analyze_directory(
         .map(|n| n.to_string_lossy().to_string())
         .unwrap_or_else(|| root_path.clone());
 
-    let (root_size, root_count) = {
+    let (root_apparent, root_allocated, root_count) = if rules.is_active() || count_hardlinks_once {
+        (None, None, 0)
+    } else {
         let cache = state.size_cache.lock().unwrap();
-        if let Some((s, c)) = cache.get(&root_path) {
-            (Some(*s), *c)
+        if let Some(entry) = cache.get(&root_path) {
+            (Some(entry.apparent_size), Some(entry.allocated_size), entry.file_count)
         } else {
-            (None, 0)
+            (None, None, 0)
         }
     };
 
+    let root_size = match size_mode {
+        SizeMode::Apparent => root_apparent,
+        SizeMode::Allocated => root_allocated,
+    };
+
     Ok(FileNode {
         name,
         path: root_path,
         size: root_size,
         base_size: current_dir_base_size,
+        apparent_size: root_apparent,
+        allocated_size: root_allocated,
         is_dir: true,
         file_count: root_count,
         children: Some(children),
+        excluded: false,
+        kind: FileKind::Directory,
     })
 }
 
+/// 为导出同步、并行地构建完整目录树，每个节点都带有确定的大小，而不是依赖
+/// `AppState` 里的后台增量缓存——那份缓存在扫描仍在进行时可能只有部分结果。
+/// 唯一的例外是无法读取元数据的条目，其 size 为 None。
+/// Synchronously and in parallel build a complete directory tree for export, where every
+/// node carries a definite size, instead of relying on `AppState`'s background incremental
+/// cache — which may still hold only a partial result while a scan is in flight. The one
+/// exception is an entry whose metadata can't be read, which gets a size of `None`.
+fn build_export_tree(path_obj: &Path, size_mode: SizeMode) -> FileNode {
+    let name = path_obj
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path_obj.to_string_lossy().to_string());
+    let path_str = path_obj.to_string_lossy().to_string();
+
+    let meta = match fs::symlink_metadata(path_obj) {
+        Ok(m) => m,
+        Err(_) => {
+            return FileNode {
+                name,
+                path: path_str,
+                size: None,
+                base_size: 0,
+                apparent_size: None,
+                allocated_size: None,
+                is_dir: false,
+                file_count: 0,
+                children: None,
+                excluded: false,
+                kind: FileKind::Regular,
+            };
+        }
+    };
+
+    let kind = classify_file_kind(&meta);
+
+    if !meta.is_dir() {
+        let apparent_size = meta.len();
+        let allocated_size = compute_allocated_size(path_obj, &meta);
+        let size = match size_mode {
+            SizeMode::Apparent => apparent_size,
+            SizeMode::Allocated => allocated_size,
+        };
+        return FileNode {
+            name,
+            path: path_str,
+            size: Some(size),
+            base_size: size,
+            apparent_size: Some(apparent_size),
+            allocated_size: Some(allocated_size),
+            is_dir: false,
+            file_count: 1,
+            children: None,
+            excluded: false,
+            kind,
+        };
+    }
+
+    let entries: Vec<PathBuf> = fs::read_dir(path_obj)
+        .map(|entries| entries.flatten().map(|e| e.path()).collect())
+        .unwrap_or_default();
+
+    let mut children: Vec<FileNode> = entries
+        .par_iter()
+        .map(|child_path| build_export_tree(child_path, size_mode))
+        .collect();
+
+    // 目录优先，其次按大小降序（None 视为 0），最后按名称，与 analyze_directory 保持一致
+    // Folders first, then size desc (None as 0), then by name, matching analyze_directory
+    children.sort_by(|a, b| {
+        if a.is_dir && !b.is_dir {
+            std::cmp::Ordering::Less
+        } else if !a.is_dir && b.is_dir {
+            std::cmp::Ordering::Greater
+        } else {
+            let size_a = a.size.unwrap_or(0);
+            let size_b = b.size.unwrap_or(0);
+            if size_a != size_b {
+                size_b.cmp(&size_a)
+            } else {
+                a.name.to_lowercase().cmp(&b.name.to_lowercase())
+            }
+        }
+    });
+
+    let base_size: u64 = children
+        .iter()
+        .filter(|c| !c.is_dir)
+        .map(|c| c.size.unwrap_or(0))
+        .sum();
+    let apparent_size: u64 = children.iter().map(|c| c.apparent_size.unwrap_or(0)).sum();
+    let allocated_size: u64 = children.iter().map(|c| c.allocated_size.unwrap_or(0)).sum();
+    let file_count: u64 = children.iter().map(|c| c.file_count).sum();
+    let size = match size_mode {
+        SizeMode::Apparent => apparent_size,
+        SizeMode::Allocated => allocated_size,
+    };
+
+    FileNode {
+        name,
+        path: path_str,
+        size: Some(size),
+        base_size,
+        apparent_size: Some(apparent_size),
+        allocated_size: Some(allocated_size),
+        is_dir: true,
+        file_count,
+        children: Some(children),
+        excluded: false,
+        kind: FileKind::Directory,
+    }
+}
+
+/// 深度优先展平成 (path, is_dir, size, file_count, depth) 行，供 CSV 导出使用
+/// Flatten depth-first into (path, is_dir, size, file_count, depth) rows for CSV export.
+fn flatten_tree(node: &FileNode, depth: u64, rows: &mut Vec<(String, bool, u64, u64, u64)>) {
+    rows.push((
+        node.path.clone(),
+        node.is_dir,
+        node.size.unwrap_or(0),
+        node.file_count,
+        depth,
+    ));
+    if let Some(children) = &node.children {
+        for child in children {
+            flatten_tree(child, depth + 1, rows);
+        }
+    }
+}
+
+/// 对字段做最基本的 CSV 转义：包含逗号、引号或换行时加引号并转义内部引号
+/// Minimal CSV field escaping: quote the field and escape inner quotes when it contains a
+/// comma, a quote, or a newline.
+fn csv_escape(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// 把展平后的行渲染成 CSV 文本，带表头
+/// Render flattened rows into CSV text, with a header row.
+fn rows_to_csv(rows: &[(String, bool, u64, u64, u64)]) -> String {
+    let mut csv = String::from("path,is_dir,size,file_count,depth\n");
+    for (path, is_dir, size, file_count, depth) in rows {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            csv_escape(path),
+            is_dir,
+            size,
+            file_count,
+            depth
+        ));
+    }
+    csv
+}
+
+/// 把扫描结果导出为 JSON 或 CSV；树是为导出专门同步构建的（见 build_export_tree），
+/// 所以导出的大小始终是完整结果，不会出现仍在计算中的部分结果；只有无法读取元数据的
+/// 条目 size 为 None。
+/// `top_n`（仅对 CSV 有效）只保留按 size 降序排列的前 N 行，类似 czkawka 的结果导出，
+/// 便于生成报告或进一步用脚本处理。
+/// Export scan results to JSON or CSV. The tree is built synchronously just for the export
+/// (see `build_export_tree`), so exported sizes are always a complete result, never a
+/// still-calculating partial one; only an entry whose metadata can't be read has a `None`
+/// size. `top_n` (CSV only) keeps just the top N rows sorted
+/// descending by size, mirroring czkawka's result export, for reporting or scripting.
+#[tauri::command]
+async fn export_scan(
+    path: String,
+    format: ExportFormat,
+    out_file: String,
+    size_mode: SizeMode,
+    top_n: Option<usize>,
+) -> Result<(), String> {
+    let root_path = normalize_path_string(&path);
+    let path_obj = Path::new(&root_path);
+    let tree = build_export_tree(path_obj, size_mode);
+
+    let contents = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&tree).map_err(|e| e.to_string())?,
+        ExportFormat::Csv => {
+            let mut rows = Vec::new();
+            flatten_tree(&tree, 0, &mut rows);
+            if let Some(n) = top_n {
+                rows.sort_by(|a, b| b.2.cmp(&a.2));
+                rows.truncate(n);
+            }
+            rows_to_csv(&rows)
+        }
+    };
+
+    fs::write(&out_file, contents).map_err(|e| e.to_string())
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .setup(|app| {
-            // 缓存仅用于加速
-            let size_cache = Arc::new(Mutex::new(HashMap::new()));
+            // 缓存从应用数据目录加载，这样未发生变化的子树在下次启动时也能直接命中
+            // The cache is loaded from the app data directory, so unchanged subtrees hit
+            // immediately even across app restarts.
+            let cache_file = app
+                .path()
+                .app_data_dir()
+                .ok()
+                .map(|dir| dir.join(CACHE_FILE_NAME));
+            let loaded_cache = cache_file
+                .as_deref()
+                .map(load_persisted_cache)
+                .unwrap_or_default();
+            let size_cache = Arc::new(Mutex::new(loaded_cache));
 
             let in_progress = Arc::new(Mutex::new(HashSet::new()));
+            let cancel_flags = Arc::new(Mutex::new(HashMap::new()));
+            let pending_updates = Arc::new(Mutex::new(HashMap::new()));
+            let tick_counter = Arc::new(AtomicUsize::new(0));
+            let thread_pool = Arc::new(Mutex::new(Arc::new(build_thread_pool(0))));
+            let cache_dirty = Arc::new(AtomicBool::new(false));
+
+            // 定期检查缓存是否被标记为 dirty，只有真的发生了变化才落盘，避免空闲时
+            // 也反复重写 size-cache.json
+            // Periodically check whether the cache was marked dirty, only flushing it to disk
+            // when something actually changed, so idle sessions don't keep rewriting
+            // size-cache.json.
+            if let Some(cache_file) = cache_file.clone() {
+                let flush_cache = size_cache.clone();
+                let flush_dirty = cache_dirty.clone();
+                thread::spawn(move || loop {
+                    thread::sleep(Duration::from_secs(5));
+                    if flush_dirty.swap(false, Ordering::Relaxed) {
+                        let snapshot = flush_cache.lock().unwrap().clone();
+                        persist_cache(&cache_file, &snapshot);
+                    }
+                });
+            }
+
+            // 单独的 ticker 线程按固定间隔批量推送目录大小更新，避免大型目录树
+            // 逐目录发送事件把 Tauri 桥打爆
+            // A dedicated ticker thread flushes pending directory size updates at a fixed
+            // interval instead of emitting one event per directory, so large trees don't
+            // flood the Tauri bridge.
+            let ticker_app_handle = app.handle().clone();
+            let ticker_pending_updates = pending_updates.clone();
+            let ticker_tick_counter = tick_counter.clone();
+            thread::spawn(move || loop {
+                thread::sleep(Duration::from_millis(100));
+
+                let batch: Vec<SizeUpdate> = {
+                    let mut pending = ticker_pending_updates.lock().unwrap();
+                    if pending.is_empty() {
+                        continue;
+                    }
+                    pending
+                        .drain()
+                        .map(|(path, (apparent_size, allocated_size, file_count))| SizeUpdate {
+                            path,
+                            apparent_size,
+                            allocated_size,
+                            file_count,
+                        })
+                        .collect()
+                };
+
+                ticker_tick_counter.fetch_add(1, Ordering::Relaxed);
+                let _ = ticker_app_handle.emit("folder-size-batch-updated", batch);
+            });
+
             app.manage(AppState {
                 size_cache,
                 in_progress,
+                cancel_flags,
+                pending_updates,
+                tick_counter,
+                thread_pool,
+                cache_file,
+                cache_dirty,
             });
 
             Ok(())
@@ -327,8 +1377,21 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             analyze_directory,
-            open_in_explorer
+            open_in_explorer,
+            find_duplicates,
+            cancel_scan,
+            set_thread_count,
+            export_scan
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            if let tauri::RunEvent::Exit = event {
+                let state = app_handle.state::<AppState>();
+                if let Some(cache_file) = &state.cache_file {
+                    let snapshot = state.size_cache.lock().unwrap().clone();
+                    persist_cache(cache_file, &snapshot);
+                }
+            }
+        });
 }